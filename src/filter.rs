@@ -0,0 +1,163 @@
+use crate::response::{JsHeader, JsResponse};
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+/// A view of the request available to filters before the JS handler runs.
+/// Borrows directly from the underlying `http::request::Parts` rather than
+/// cloning, since filters only need to inspect the request.
+pub struct RequestParts<'a> {
+    pub method: &'a http::Method,
+    pub uri: &'a http::Uri,
+    pub headers: &'a http::HeaderMap,
+    pub client_address: &'a str,
+}
+
+/// What a filter wants to happen to a request after `on_request` runs
+pub enum FilterAction {
+    /// Proceed to the next filter (or the JS handler if this was the last one)
+    Continue,
+    /// Skip the JS handler entirely and send this response
+    ShortCircuit(JsResponse),
+}
+
+/// A request/response hook that runs in Rust, before the JS handler is
+/// invoked and again as the response is built, so latency-critical logic
+/// (auth rejection, rate limiting, CORS headers) can run without a
+/// threadsafe-function round-trip to JS.
+pub trait Filter: Send + Sync {
+    /// Called before the JS handler. Default: always continue.
+    fn on_request(&self, _parts: &RequestParts) -> FilterAction {
+        FilterAction::Continue
+    }
+
+    /// Called while building the response, for every response, including one
+    /// returned by a short-circuiting `on_request`. Default: no-op.
+    fn on_response(&self, _response: &mut JsResponse) {}
+}
+
+/// How many `on_request` calls between sweeps of expired buckets in
+/// `RateLimiterFilter`. Keeps the map bounded by recently-active clients
+/// under normal traffic.
+const RATE_LIMITER_SWEEP_INTERVAL: u32 = 1024;
+
+/// Hard cap on the number of distinct client addresses `RateLimiterFilter`
+/// tracks at once. The time-based sweep above only reclaims entries that
+/// have aged out of the window, which does nothing against a flood of
+/// unique (e.g. spoofed or rotating) addresses within a single window; once
+/// the map is at capacity, the oldest bucket is evicted to make room for a
+/// new address instead of letting the map grow without bound.
+const RATE_LIMITER_MAX_BUCKETS: usize = 65536;
+
+/// Rejects a client address once it exceeds `max_requests` within a fixed
+/// `window`, responding `429 Too Many Requests`. Each address's window starts
+/// at its first request and restarts with the first request after it has
+/// fully elapsed.
+pub struct RateLimiterFilter {
+    max_requests: u32,
+    window: Duration,
+    buckets: Mutex<HashMap<String, (Instant, u32)>>,
+    requests_since_sweep: AtomicU32,
+}
+
+impl RateLimiterFilter {
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        Self {
+            max_requests,
+            window,
+            buckets: Mutex::new(HashMap::new()),
+            requests_since_sweep: AtomicU32::new(0),
+        }
+    }
+}
+
+impl Filter for RateLimiterFilter {
+    fn on_request(&self, parts: &RequestParts) -> FilterAction {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock();
+
+        if self.requests_since_sweep.fetch_add(1, Ordering::Relaxed) + 1
+            >= RATE_LIMITER_SWEEP_INTERVAL
+        {
+            self.requests_since_sweep.store(0, Ordering::Relaxed);
+            buckets.retain(|_, (started, _)| now.duration_since(*started) <= self.window);
+        }
+
+        if buckets.len() >= RATE_LIMITER_MAX_BUCKETS && !buckets.contains_key(parts.client_address)
+        {
+            // At capacity and this is a new address: evict the
+            // least-recently-started bucket rather than let the map grow
+            // unbounded, e.g. under a flood of unique client addresses.
+            if let Some(oldest) = buckets
+                .iter()
+                .min_by_key(|(_, (started, _))| *started)
+                .map(|(addr, _)| addr.clone())
+            {
+                buckets.remove(&oldest);
+            }
+        }
+
+        let entry = buckets
+            .entry(parts.client_address.to_string())
+            .or_insert((now, 0));
+
+        if now.duration_since(entry.0) > self.window {
+            *entry = (now, 0);
+        }
+        entry.1 += 1;
+
+        if entry.1 > self.max_requests {
+            FilterAction::ShortCircuit(JsResponse::text("Too Many Requests", 429))
+        } else {
+            FilterAction::Continue
+        }
+    }
+}
+
+/// Rejects requests whose method isn't in an allowlist, responding
+/// `405 Method Not Allowed`.
+pub struct MethodAllowlistFilter {
+    allowed: HashSet<String>,
+}
+
+impl MethodAllowlistFilter {
+    pub fn new(allowed: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            allowed: allowed.into_iter().map(|m| m.to_uppercase()).collect(),
+        }
+    }
+}
+
+impl Filter for MethodAllowlistFilter {
+    fn on_request(&self, parts: &RequestParts) -> FilterAction {
+        if self.allowed.contains(parts.method.as_str()) {
+            FilterAction::Continue
+        } else {
+            FilterAction::ShortCircuit(JsResponse::text("Method Not Allowed", 405))
+        }
+    }
+}
+
+/// Injects a fixed set of headers (e.g. `Cache-Control`, CORS headers) into
+/// every response.
+pub struct StaticHeadersFilter {
+    headers: Vec<(String, String)>,
+}
+
+impl StaticHeadersFilter {
+    pub fn new(headers: Vec<(String, String)>) -> Self {
+        Self { headers }
+    }
+}
+
+impl Filter for StaticHeadersFilter {
+    fn on_response(&self, response: &mut JsResponse) {
+        for (name, value) in &self.headers {
+            response.headers.push(JsHeader {
+                name: name.clone(),
+                value: value.clone(),
+            });
+        }
+    }
+}
@@ -1,17 +1,23 @@
 #![deny(clippy::all)]
 
+mod filter;
 mod request;
 mod response;
 mod server;
+mod websocket;
 
 use napi::bindgen_prelude::*;
 use napi::threadsafe_function::ThreadsafeFunction;
 use napi_derive::napi;
 use std::sync::Arc;
+use std::time::Duration;
 
+pub use filter::{Filter, FilterAction, RequestParts};
+use filter::{MethodAllowlistFilter, RateLimiterFilter, StaticHeadersFilter};
 pub use request::RequestContext;
 pub use response::{JsHeader, JsResponse};
 pub use server::{ServerConfig, ServerInner};
+pub use websocket::WebSocketHandle;
 
 /// Server options passed from JavaScript
 #[napi(object)]
@@ -27,8 +33,26 @@ pub struct ServerOptions {
     pub max_connections: Option<u32>,
     /// Request body size limit in bytes (default: 10MB)
     pub max_body_size: Option<u32>,
+    /// Bodies with a declared length at or above this are streamed to the
+    /// handler instead of buffered up front (default: `max_body_size`)
+    pub stream_threshold: Option<u32>,
     /// Request timeout in milliseconds (default: 30000)
     pub timeout: Option<u32>,
+    /// Automatic response compression (disabled unless provided)
+    pub compression: Option<CompressionOptions>,
+    /// Serve HTTPS using the given certificate/key instead of plaintext HTTP
+    pub tls: Option<TlsOptions>,
+    /// Disable Nagle's algorithm on accepted sockets (default: true)
+    pub tcp_nodelay: Option<bool>,
+    /// TCP keepalive tuning via SO_KEEPALIVE (disabled unless provided)
+    pub tcp_keepalive: Option<TcpKeepaliveOptions>,
+    /// Enable TCP_FASTOPEN with the given queue length (disabled unless provided)
+    pub tcp_fastopen: Option<u32>,
+    /// Listen backlog size (default: 8192)
+    pub backlog: Option<u32>,
+    /// Built-in request/response filters that run in Rust before the JS
+    /// handler is invoked (disabled unless provided)
+    pub filters: Option<FilterOptions>,
 }
 
 impl Default for ServerOptions {
@@ -38,12 +62,85 @@ impl Default for ServerOptions {
             host: Some("0.0.0.0".to_string()),
             max_connections: Some(65536),
             max_body_size: Some(10 * 1024 * 1024), // 10MB
+            stream_threshold: None,
             timeout: Some(30000),
             reuse_port: Some(false),
+            compression: None,
+            tls: None,
+            tcp_nodelay: Some(true),
+            tcp_keepalive: None,
+            tcp_fastopen: None,
+            backlog: Some(8192),
+            filters: None,
         }
     }
 }
 
+/// Built-in request/response filter configuration. Each field enables one
+/// filter independently; for anything more custom, implement `Filter` in
+/// Rust and register it with `ServerInner::add_filter`.
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct FilterOptions {
+    /// Reject a client address once it exceeds this many requests within
+    /// `rate_limit_window_secs`, responding 429 (disabled unless set)
+    pub rate_limit_per_window: Option<u32>,
+    /// Fixed window size in seconds for `rate_limit_per_window`, starting at a
+    /// client's first request (default: 60)
+    pub rate_limit_window_secs: Option<u32>,
+    /// Only allow these HTTP methods, responding 405 to everything else
+    /// (disabled unless set)
+    pub allowed_methods: Option<Vec<String>>,
+    /// Extra headers injected into every response, e.g. CORS or
+    /// `Cache-Control` (disabled unless set)
+    pub response_headers: Option<Vec<JsHeader>>,
+}
+
+/// TLS certificate/key paths for HTTPS listeners (PEM-encoded)
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct TlsOptions {
+    /// Path to a PEM-encoded certificate chain
+    pub cert_path: String,
+    /// Path to a PEM-encoded private key
+    pub key_path: String,
+}
+
+/// TCP keepalive tuning (SO_KEEPALIVE)
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct TcpKeepaliveOptions {
+    /// Seconds of idleness before the first keepalive probe (default: 60)
+    pub idle_secs: Option<u32>,
+    /// Seconds between subsequent probes (default: 10)
+    pub interval_secs: Option<u32>,
+    /// Number of unacknowledged probes before the connection is dropped (default: 3)
+    pub retries: Option<u32>,
+}
+
+/// Automatic gzip/brotli compression options for response bodies
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct CompressionOptions {
+    /// Enable automatic compression (default: true once this object is set)
+    pub enabled: Option<bool>,
+    /// Minimum response body size in bytes before compressing (default: 1024)
+    pub min_size: Option<u32>,
+}
+
+/// Result of a `Server::close` call
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct CloseResult {
+    /// True if all in-flight requests finished before the deadline (or no
+    /// deadline was given)
+    pub drained: bool,
+    /// Requests still in flight when the deadline expired (0 if drained)
+    pub pending_requests: u32,
+    /// Connections forcibly aborted when the deadline expired (0 if drained)
+    pub aborted_connections: u32,
+}
+
 /// Server statistics
 #[napi(object)]
 #[derive(Debug, Clone, Default)]
@@ -56,6 +153,12 @@ pub struct ServerStats {
     pub requests_per_second: f64,
     /// Average latency in milliseconds
     pub avg_latency_ms: f64,
+    /// Average observed TCP round-trip time in microseconds across accepted
+    /// connections (0 if unavailable on this platform)
+    pub avg_tcp_rtt_us: f64,
+    /// Total TCP retransmits observed across accepted connections (0 if
+    /// unavailable on this platform)
+    pub tcp_retransmits: f64,
 }
 
 /// High-performance HTTP server with Fetch Event API
@@ -71,6 +174,7 @@ impl Server {
     #[napi(constructor)]
     pub fn new(options: ServerOptions) -> Result<Self> {
         let inner = Arc::new(ServerInner::new(ServerConfig::from(&options)));
+        register_builtin_filters(&inner, options.filters.as_ref());
         Ok(Self { inner, options })
     }
 
@@ -100,11 +204,13 @@ impl Server {
         })
     }
 
-    /// Gracefully shutdown the server
+    /// Gracefully shutdown the server: stop accepting new connections and
+    /// wait for in-flight requests to finish. If `deadline_ms` is given and
+    /// elapses first, remaining connections are forcibly aborted and the
+    /// result reports how many requests/connections were dropped.
     #[napi]
-    pub async fn close(&self) -> Result<()> {
-        self.inner.close().await;
-        Ok(())
+    pub async fn close(&self, deadline_ms: Option<u32>) -> Result<CloseResult> {
+        Ok(self.inner.close(deadline_ms).await)
     }
 
     /// Get current server statistics
@@ -113,3 +219,30 @@ impl Server {
         self.inner.stats()
     }
 }
+
+/// Translate `FilterOptions` into registered built-in `Filter`s, in the same
+/// order they're listed on the struct.
+fn register_builtin_filters(inner: &Arc<ServerInner>, options: Option<&FilterOptions>) {
+    let Some(options) = options else {
+        return;
+    };
+
+    if let Some(max_requests) = options.rate_limit_per_window {
+        let window = Duration::from_secs(options.rate_limit_window_secs.unwrap_or(60) as u64);
+        inner.add_filter(Box::new(RateLimiterFilter::new(max_requests, window)));
+    }
+
+    if let Some(allowed_methods) = &options.allowed_methods {
+        inner.add_filter(Box::new(MethodAllowlistFilter::new(
+            allowed_methods.clone(),
+        )));
+    }
+
+    if let Some(response_headers) = &options.response_headers {
+        let headers = response_headers
+            .iter()
+            .map(|h| (h.name.clone(), h.value.clone()))
+            .collect();
+        inner.add_filter(Box::new(StaticHeadersFilter::new(headers)));
+    }
+}
@@ -1,9 +1,26 @@
-use crate::response::JsResponse;
+use crate::response::{JsHeader, JsResponse, ResponseStream};
+use crate::websocket::{self, WebSocketHandle};
+use bytes::Bytes;
 use napi::bindgen_prelude::*;
 use napi_derive::napi;
 use parking_lot::Mutex;
 use std::sync::Arc;
-use tokio::sync::oneshot;
+use tokio::sync::{mpsc, oneshot};
+
+/// Channel payload for `RequestContext::response_sender`, covering the
+/// fully-buffered reply path (`respond`), the streaming path
+/// (`respond_stream`), and the WebSocket handshake path (`upgrade`).
+pub(crate) enum ResponseMessage {
+    Buffered(JsResponse),
+    Streaming {
+        status: u16,
+        headers: Vec<JsHeader>,
+        receiver: mpsc::Receiver<Bytes>,
+    },
+    Upgrade {
+        accept: String,
+    },
+}
 
 /// Header as a two-element array [name, value] for JavaScript
 #[napi(object)]
@@ -27,9 +44,20 @@ pub struct RequestContext {
     pub(crate) body: Option<Buffer>,
     /// Client IP address
     pub(crate) client_address: String,
+    /// Whether this request arrived over TLS
+    pub(crate) encrypted: bool,
+    /// Incremental body reader, set instead of `body` when the request body
+    /// is being streamed rather than buffered (skipped from napi)
+    #[napi(skip)]
+    pub(crate) body_handle: Mutex<Option<BodyHandle>>,
+    /// Internal: the hyper upgrade future for this connection, present only
+    /// when the request looked like a valid WebSocket handshake (skipped
+    /// from napi)
+    #[napi(skip)]
+    pub(crate) upgrade_source: Mutex<Option<hyper::upgrade::OnUpgrade>>,
     /// Internal: Channel to send response back to Rust (skipped from napi)
     #[napi(skip)]
-    pub response_sender: Option<Arc<Mutex<Option<oneshot::Sender<JsResponse>>>>>,
+    pub(crate) response_sender: Option<Arc<Mutex<Option<oneshot::Sender<ResponseMessage>>>>>,
 }
 
 #[napi]
@@ -66,15 +94,82 @@ impl RequestContext {
         self.client_address.clone()
     }
 
+    /// Whether this request arrived over TLS
+    #[napi(getter)]
+    pub fn get_encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    /// Take the incremental body reader for this request (or null if the
+    /// body was small enough to be buffered up front and is available via
+    /// `body` instead). May only be taken once.
+    #[napi(js_name = "takeBodyHandle")]
+    pub fn take_body_handle(&self) -> Option<BodyHandle> {
+        self.body_handle.lock().take()
+    }
+
     /// Send a response back to the client
     /// This must be called exactly once per request
     #[napi]
     pub fn respond(&self, response: JsResponse) -> Result<()> {
+        self.send_response(ResponseMessage::Buffered(response))
+    }
+
+    /// Begin a streamed response: send the status/headers immediately and
+    /// return a `ResponseStream` the caller can push body chunks into with
+    /// `write()`, finishing with `end()`. Like `respond`, this may only be
+    /// called once per request.
+    #[napi]
+    pub fn respond_stream(&self, status: u16, headers: Vec<JsHeader>) -> Result<ResponseStream> {
+        let (tx, rx) = mpsc::channel::<Bytes>(16);
+        self.send_response(ResponseMessage::Streaming {
+            status,
+            headers,
+            receiver: rx,
+        })?;
+        Ok(ResponseStream::new(tx))
+    }
+
+    /// Complete a WebSocket handshake: sends the `101 Switching Protocols`
+    /// response and hands back a `WebSocketHandle` for framing data once the
+    /// underlying connection has actually been upgraded. Errors if this
+    /// request was not a valid WebSocket upgrade (see `process_request`) or
+    /// if `upgrade`/`respond`/`respond_stream` was already called.
+    #[napi]
+    pub async fn upgrade(&self) -> Result<WebSocketHandle> {
+        let key = self
+            .headers
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case("sec-websocket-key"))
+            .map(|h| h.value.clone())
+            .ok_or_else(|| {
+                Error::new(Status::GenericFailure, "Missing Sec-WebSocket-Key header")
+            })?;
+
+        let on_upgrade = self.upgrade_source.lock().take().ok_or_else(|| {
+            Error::new(
+                Status::GenericFailure,
+                "Request is not a WebSocket upgrade",
+            )
+        })?;
+
+        self.send_response(ResponseMessage::Upgrade {
+            accept: websocket::accept_key(&key),
+        })?;
+
+        let upgraded = on_upgrade
+            .await
+            .map_err(|e| Error::new(Status::GenericFailure, format!("Upgrade failed: {}", e)))?;
+
+        Ok(WebSocketHandle::new(upgraded))
+    }
+
+    fn send_response(&self, message: ResponseMessage) -> Result<()> {
         if let Some(sender_arc) = &self.response_sender {
             let mut sender_guard = sender_arc.lock();
             if let Some(sender) = sender_guard.take() {
                 sender
-                    .send(response)
+                    .send(message)
                     .map_err(|_| Error::new(Status::GenericFailure, "Failed to send response"))?;
                 Ok(())
             } else {
@@ -94,6 +189,7 @@ impl RequestContext {
 #[napi]
 pub struct BodyHandle {
     receiver: Option<tokio::sync::mpsc::Receiver<bytes::Bytes>>,
+    truncated: Arc<std::sync::atomic::AtomicBool>,
 }
 
 #[napi]
@@ -118,6 +214,13 @@ impl BodyHandle {
     pub fn is_closed(&self) -> bool {
         self.receiver.is_none()
     }
+
+    /// True if the body was cut short because it exceeded `max_body_size`.
+    /// When this is set, the server replies 413 whatever the handler returns.
+    #[napi(js_name = "isTruncated")]
+    pub fn is_truncated(&self) -> bool {
+        self.truncated.load(std::sync::atomic::Ordering::Relaxed)
+    }
 }
 
 impl BodyHandle {
@@ -125,6 +228,26 @@ impl BodyHandle {
     pub fn new(receiver: tokio::sync::mpsc::Receiver<bytes::Bytes>) -> Self {
         Self {
             receiver: Some(receiver),
+            truncated: Arc::new(std::sync::atomic::AtomicBool::new(false)),
         }
     }
+
+    /// Create a BodyHandle sharing its truncation flag with the background
+    /// task that forwards frames into `receiver`
+    pub(crate) fn with_truncated_flag(
+        receiver: tokio::sync::mpsc::Receiver<bytes::Bytes>,
+        truncated: Arc<std::sync::atomic::AtomicBool>,
+    ) -> Self {
+        Self {
+            receiver: Some(receiver),
+            truncated,
+        }
+    }
+
+    /// Clone of the shared truncation flag, so the caller can enforce a hard
+    /// 413 on the streaming request-body path instead of relying on the
+    /// handler to check `isTruncated()` itself.
+    pub(crate) fn truncated_flag(&self) -> Arc<std::sync::atomic::AtomicBool> {
+        Arc::clone(&self.truncated)
+    }
 }
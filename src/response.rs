@@ -1,5 +1,8 @@
+use bytes::Bytes;
 use napi::bindgen_prelude::*;
 use napi_derive::napi;
+use parking_lot::Mutex;
+use tokio::sync::mpsc;
 
 /// Header key-value pair for use in JsResponse
 #[napi(object)]
@@ -74,3 +77,44 @@ impl Default for JsResponse {
         Self::empty(200)
     }
 }
+
+/// Handle for a response whose body is streamed chunk-by-chunk instead of
+/// fully buffered up front. Created via `RequestContext::respond_stream`.
+#[napi]
+pub struct ResponseStream {
+    sender: Mutex<Option<mpsc::Sender<Bytes>>>,
+}
+
+#[napi]
+impl ResponseStream {
+    /// Push a chunk of the response body to the client
+    #[napi]
+    pub async fn write(&self, chunk: Buffer) -> Result<()> {
+        let sender = self.sender.lock().clone();
+        match sender {
+            Some(tx) => tx
+                .send(Bytes::from(chunk.to_vec()))
+                .await
+                .map_err(|_| Error::new(Status::GenericFailure, "Response stream closed")),
+            None => Err(Error::new(
+                Status::GenericFailure,
+                "Response stream already ended",
+            )),
+        }
+    }
+
+    /// Signal that the response body is complete
+    #[napi]
+    pub fn end(&self) -> Result<()> {
+        self.sender.lock().take();
+        Ok(())
+    }
+}
+
+impl ResponseStream {
+    pub(crate) fn new(sender: mpsc::Sender<Bytes>) -> Self {
+        Self {
+            sender: Mutex::new(Some(sender)),
+        }
+    }
+}
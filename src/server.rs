@@ -1,10 +1,15 @@
-use crate::request::{HeaderPair, RequestContext};
-use crate::response::JsResponse;
+use crate::filter::{Filter, FilterAction, RequestParts};
+use crate::request::{BodyHandle, HeaderPair, RequestContext, ResponseMessage};
+use crate::response::{JsHeader, JsResponse};
+use crate::CloseResult;
 use crate::ServerOptions;
 use crate::ServerStats;
+use crate::TcpKeepaliveOptions;
+use crate::TlsOptions;
 
 use axum::{
     body::Body,
+    extract::connect_info::IntoMakeServiceWithConnectInfo,
     extract::{ConnectInfo, Request},
     http::StatusCode,
     response::Response,
@@ -13,15 +18,22 @@ use axum::{
 };
 use bytes::Bytes;
 use http_body_util::BodyExt;
+use hyper::body::Incoming;
+use hyper_util::rt::{TokioExecutor, TokioIo};
 use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
 use parking_lot::{Mutex, RwLock};
 use socket2::{Domain, Protocol, Socket, Type};
+use std::io::Write;
 use std::net::SocketAddr;
 use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
 use tokio::net::TcpListener;
 use tokio::sync::{oneshot, Notify};
+use tokio_rustls::TlsAcceptor;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::StreamExt;
+use tower::Service;
 
 /// Server configuration derived from ServerOptions
 #[derive(Debug, Clone)]
@@ -31,7 +43,19 @@ pub struct ServerConfig {
     pub reuse_port: bool,
     pub max_connections: u32,
     pub max_body_size: u32,
+    /// Bodies with a declared length at or above this are streamed to the
+    /// handler instead of buffered up front (see `should_stream_body`)
+    pub stream_threshold: u64,
     pub timeout_ms: u32,
+    pub compression: CompressionConfig,
+    pub tls: Option<TlsConfig>,
+    /// "https" when `tls` is set, "http" otherwise — the scheme reported in
+    /// `RequestContext::url`
+    pub scheme: &'static str,
+    pub tcp_nodelay: bool,
+    pub tcp_keepalive: Option<TcpKeepaliveConfig>,
+    pub tcp_fastopen_backlog: Option<u32>,
+    pub backlog: u32,
 }
 
 impl From<&ServerOptions> for ServerConfig {
@@ -42,7 +66,74 @@ impl From<&ServerOptions> for ServerConfig {
             reuse_port: opts.reuse_port.unwrap_or(false),
             max_connections: opts.max_connections.unwrap_or(65536),
             max_body_size: opts.max_body_size.unwrap_or(10 * 1024 * 1024),
+            stream_threshold: opts
+                .stream_threshold
+                .map(|v| v as u64)
+                .unwrap_or(opts.max_body_size.unwrap_or(10 * 1024 * 1024) as u64),
             timeout_ms: opts.timeout.unwrap_or(30000),
+            compression: match &opts.compression {
+                Some(c) => CompressionConfig {
+                    enabled: c.enabled.unwrap_or(true),
+                    min_size: c.min_size.unwrap_or(1024) as usize,
+                },
+                None => CompressionConfig::default(),
+            },
+            scheme: if opts.tls.is_some() { "https" } else { "http" },
+            tls: opts.tls.as_ref().map(TlsConfig::from),
+            tcp_nodelay: opts.tcp_nodelay.unwrap_or(true),
+            tcp_keepalive: opts.tcp_keepalive.as_ref().map(TcpKeepaliveConfig::from),
+            tcp_fastopen_backlog: opts.tcp_fastopen,
+            backlog: opts.backlog.unwrap_or(8192),
+        }
+    }
+}
+
+/// Derived TCP keepalive settings (see `crate::TcpKeepaliveOptions`)
+#[derive(Debug, Clone)]
+pub struct TcpKeepaliveConfig {
+    pub idle_secs: u32,
+    pub interval_secs: u32,
+    pub retries: u32,
+}
+
+impl From<&TcpKeepaliveOptions> for TcpKeepaliveConfig {
+    fn from(opts: &TcpKeepaliveOptions) -> Self {
+        Self {
+            idle_secs: opts.idle_secs.unwrap_or(60),
+            interval_secs: opts.interval_secs.unwrap_or(10),
+            retries: opts.retries.unwrap_or(3),
+        }
+    }
+}
+
+/// Derived TLS settings (see `crate::TlsOptions`)
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+}
+
+impl From<&TlsOptions> for TlsConfig {
+    fn from(opts: &TlsOptions) -> Self {
+        Self {
+            cert_path: opts.cert_path.clone(),
+            key_path: opts.key_path.clone(),
+        }
+    }
+}
+
+/// Derived automatic-compression settings (see `crate::CompressionOptions`)
+#[derive(Debug, Clone)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+    pub min_size: usize,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_size: 1024,
         }
     }
 }
@@ -52,7 +143,22 @@ pub struct ServerInner {
     config: ServerConfig,
     handler: RwLock<Option<ThreadsafeFunction<RequestContext, ErrorStrategy::Fatal>>>,
     shutdown_notify: Arc<Notify>,
+    /// Notified each time an accepted connection closes, so a paused accept
+    /// loop can re-check whether it has drained below the low-water mark
+    connection_closed: Notify,
+    /// Number of requests currently being handled, for graceful-shutdown
+    /// draining
+    in_flight: AtomicU32,
+    /// Notified each time `in_flight` transitions to zero
+    drained_notify: Notify,
+    /// Live connections' abort handles, keyed by a monotonic id, so `close`
+    /// can forcibly cut them off once its deadline expires
+    connections: Mutex<std::collections::HashMap<u64, tokio::task::AbortHandle>>,
+    next_connection_id: AtomicU64,
     stats: ServerStatsInner,
+    /// Ordered request/response filters, run before the JS handler and again
+    /// while building the response (see `crate::filter`)
+    filters: RwLock<Vec<Box<dyn Filter>>>,
 }
 
 /// Internal statistics tracking
@@ -60,6 +166,9 @@ struct ServerStatsInner {
     active_connections: AtomicU32,
     total_requests: AtomicU64,
     total_latency_us: AtomicU64,
+    tcp_rtt_us_sum: AtomicU64,
+    tcp_rtt_samples: AtomicU64,
+    tcp_retransmits: AtomicU64,
 }
 
 impl ServerStatsInner {
@@ -68,17 +177,143 @@ impl ServerStatsInner {
             active_connections: AtomicU32::new(0),
             total_requests: AtomicU64::new(0),
             total_latency_us: AtomicU64::new(0),
+            tcp_rtt_us_sum: AtomicU64::new(0),
+            tcp_rtt_samples: AtomicU64::new(0),
+            tcp_retransmits: AtomicU64::new(0),
         }
     }
 }
 
+/// Enable `TCP_FASTOPEN` with the given queue length. Stable `socket2`
+/// doesn't expose this as a method, so it's set directly via `setsockopt`.
+#[cfg(target_os = "linux")]
+fn set_tcp_fastopen(socket: &Socket, backlog: u32) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let backlog = backlog as libc::c_int;
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_FASTOPEN,
+            &backlog as *const _ as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// Token identifying a connection's socket for `sample_tcp_info_fd`, a
+/// dup'd raw fd on Linux so `TCP_INFO` can be sampled after the connection
+/// closes without racing the original `TcpStream`'s lifetime (and a no-op
+/// unit value elsewhere, since `TCP_INFO` isn't portable).
+#[cfg(target_os = "linux")]
+type TcpInfoToken = std::os::unix::io::RawFd;
+#[cfg(not(target_os = "linux"))]
+type TcpInfoToken = ();
+
+/// Duplicate a freshly accepted connection's raw fd so its `TCP_INFO` can be
+/// read later, once the connection has actually exchanged some data, rather
+/// than immediately after `accept()` (when it would just reflect the
+/// handshake).
+#[cfg(target_os = "linux")]
+fn dup_fd_for_tcp_info(stream: &tokio::net::TcpStream) -> TcpInfoToken {
+    use std::os::unix::io::AsRawFd;
+    unsafe { libc::dup(stream.as_raw_fd()) }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn dup_fd_for_tcp_info(_stream: &tokio::net::TcpStream) -> TcpInfoToken {}
+
+/// Sample `TCP_INFO` (round-trip time, retransmits) via a fd obtained from
+/// `dup_fd_for_tcp_info`, fold it into the running stats, and close the
+/// duplicated fd. Linux-only; a no-op elsewhere since `TCP_INFO` isn't
+/// portable.
+#[cfg(target_os = "linux")]
+fn sample_tcp_info_fd(fd: TcpInfoToken, stats: &ServerStatsInner) {
+    if fd < 0 {
+        return;
+    }
+
+    let mut info: libc::tcp_info = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::tcp_info>() as libc::socklen_t;
+
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::IPPROTO_TCP,
+            libc::TCP_INFO,
+            &mut info as *mut _ as *mut libc::c_void,
+            &mut len,
+        )
+    };
+
+    if ret == 0 {
+        stats
+            .tcp_rtt_us_sum
+            .fetch_add(info.tcpi_rtt as u64, Ordering::Relaxed);
+        stats.tcp_rtt_samples.fetch_add(1, Ordering::Relaxed);
+        stats
+            .tcp_retransmits
+            .fetch_add(info.tcpi_total_retrans as u64, Ordering::Relaxed);
+    }
+
+    unsafe {
+        libc::close(fd);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn sample_tcp_info_fd(_fd: TcpInfoToken, _stats: &ServerStatsInner) {}
+
+/// Ties the `active_connections` count, `connection_closed` wake-up, and the
+/// connection's registration in `ServerInner::connections` to an accepted
+/// connection's lifetime, so dropping the connection's spawned task (for any
+/// reason, including a forced abort from `close`) always cleans up.
+struct ConnectionGuard {
+    inner: Arc<ServerInner>,
+    id: u64,
+}
+
+impl ConnectionGuard {
+    fn new(inner: Arc<ServerInner>, id: u64) -> Self {
+        Self { inner, id }
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.inner
+            .stats
+            .active_connections
+            .fetch_sub(1, Ordering::Relaxed);
+        self.inner.connections.lock().remove(&self.id);
+        self.inner.connection_closed.notify_one();
+    }
+}
+
 impl ServerInner {
+    /// How far below `max_connections` the live count must drain before the
+    /// accept loop resumes calling `accept()`
+    const LOW_WATER_MARGIN: u32 = 64;
+
     pub fn new(config: ServerConfig) -> Self {
         Self {
             config,
             handler: RwLock::new(None),
             shutdown_notify: Arc::new(Notify::new()),
+            connection_closed: Notify::new(),
+            in_flight: AtomicU32::new(0),
+            drained_notify: Notify::new(),
+            connections: Mutex::new(std::collections::HashMap::new()),
+            next_connection_id: AtomicU64::new(0),
             stats: ServerStatsInner::new(),
+            filters: RwLock::new(Vec::new()),
         }
     }
 
@@ -86,6 +321,14 @@ impl ServerInner {
         *self.handler.write() = Some(handler);
     }
 
+    /// Register a filter to run for every request, after any already
+    /// registered. Native code embedding this crate can call this directly;
+    /// built-in filters from `ServerOptions.filters` are wired up the same
+    /// way in `Server::new`.
+    pub fn add_filter(&self, filter: Box<dyn Filter>) {
+        self.filters.write().push(filter);
+    }
+
     pub async fn listen(
         self: &Arc<Self>,
         addr: &str,
@@ -113,24 +356,140 @@ impl ServerInner {
             );
 
         let listener = TcpListener::from_std(listener.into())?;
+        let make_service = app.into_make_service_with_connect_info::<SocketAddr>();
 
-        tracing::info!("Server listening on {}", addr);
+        let tls_acceptor = match &self.config.tls {
+            Some(tls) => Some(Arc::new(TlsAcceptor::from(Arc::new(build_rustls_config(
+                tls,
+            )?)))),
+            None => None,
+        };
+
+        tracing::info!("Server listening on {} ({})", addr, self.config.scheme);
 
+        let accept_inner = Arc::clone(self);
         let shutdown_notify = Arc::clone(&self.shutdown_notify);
 
-        // Use axum's serve with graceful shutdown
-        axum::serve(
-            listener,
-            app.into_make_service_with_connect_info::<SocketAddr>(),
-        )
-        .with_graceful_shutdown(async move {
-            shutdown_notify.notified().await;
-        })
-        .await?;
+        // Run our own accept loop (instead of axum::serve) so max_connections
+        // is enforced against actually-accepted sockets rather than just
+        // in-flight handler calls.
+        tokio::select! {
+            _ = accept_inner.accept_loop(listener, make_service, tls_acceptor) => {}
+            _ = shutdown_notify.notified() => {
+                tracing::info!("Shutdown signal received, no longer accepting connections");
+            }
+        }
 
         Ok(())
     }
 
+    /// Accept connections until the listener (or the whole server) shuts
+    /// down, enforcing `max_connections` with pause/resume backpressure:
+    /// once the live count reaches the cap, stop calling `accept()` and let
+    /// the OS backlog absorb new clients until the count drains back down to
+    /// the low-water mark.
+    async fn accept_loop(
+        self: Arc<Self>,
+        listener: TcpListener,
+        make_service: IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+        tls_acceptor: Option<Arc<TlsAcceptor>>,
+    ) {
+        let high_water = self.config.max_connections.max(1);
+        let low_water = high_water.saturating_sub(Self::LOW_WATER_MARGIN).max(1);
+
+        loop {
+            if self.stats.active_connections.load(Ordering::Relaxed) >= high_water {
+                tracing::warn!(
+                    "max_connections ({}) reached, pausing accept() until below {}",
+                    high_water,
+                    low_water
+                );
+                loop {
+                    self.connection_closed.notified().await;
+                    if self.stats.active_connections.load(Ordering::Relaxed) <= low_water {
+                        break;
+                    }
+                }
+            }
+
+            let (stream, peer_addr) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    tracing::warn!("Failed to accept connection: {}", e);
+                    continue;
+                }
+            };
+
+            if let Err(e) = stream.set_nodelay(self.config.tcp_nodelay) {
+                tracing::debug!("Failed to set TCP_NODELAY for {}: {}", peer_addr, e);
+            }
+            let tcp_info_fd = dup_fd_for_tcp_info(&stream);
+
+            self.stats
+                .active_connections
+                .fetch_add(1, Ordering::Relaxed);
+            let connection_id = self.next_connection_id.fetch_add(1, Ordering::Relaxed);
+
+            let mut make_service = make_service.clone();
+            // Created before the spawn and moved into the task, so a task
+            // aborted before its first poll still drops the guard.
+            let guard = ConnectionGuard::new(Arc::clone(&self), connection_id);
+            let tls_acceptor = tls_acceptor.clone();
+
+            // Hold `connections` across both the spawn and the insert below,
+            // so `ConnectionGuard::drop`'s `connections.lock().remove(..)`
+            // (which can run as soon as the task is scheduled, even before
+            // `tokio::spawn` returns here) can't race ahead of the insert
+            // and leave a stale entry behind.
+            let mut connections = self.connections.lock();
+            let join_handle = tokio::spawn(async move {
+                let guard = guard;
+
+                let tower_service = make_service.call(peer_addr).await.ok(); // IntoMakeServiceWithConnectInfo's error is Infallible
+
+                if let Some(tower_service) = tower_service {
+                    let hyper_service =
+                        hyper::service::service_fn(move |request: hyper::Request<Incoming>| {
+                            tower_service.clone().call(request)
+                        });
+
+                    let result = match tls_acceptor {
+                        Some(acceptor) => match acceptor.accept(stream).await {
+                            Ok(tls_stream) => Some(
+                                hyper_util::server::conn::auto::Builder::new(TokioExecutor::new())
+                                    .serve_connection_with_upgrades(
+                                        TokioIo::new(tls_stream),
+                                        hyper_service,
+                                    )
+                                    .await,
+                            ),
+                            Err(e) => {
+                                tracing::debug!("TLS handshake with {} failed: {}", peer_addr, e);
+                                None
+                            }
+                        },
+                        None => Some(
+                            hyper_util::server::conn::auto::Builder::new(TokioExecutor::new())
+                                .serve_connection_with_upgrades(TokioIo::new(stream), hyper_service)
+                                .await,
+                        ),
+                    };
+
+                    if let Some(Err(err)) = result {
+                        tracing::debug!("Connection from {} closed with error: {}", peer_addr, err);
+                    }
+                }
+
+                // Sample TCP_INFO now that the connection has actually
+                // exchanged data, instead of immediately after accept().
+                sample_tcp_info_fd(tcp_info_fd, &guard.inner.stats);
+            });
+
+            connections.insert(connection_id, join_handle.abort_handle());
+            drop(connections);
+        }
+    }
+
     fn create_listener(&self, addr: SocketAddr) -> std::io::Result<std::net::TcpListener> {
         let domain = if addr.is_ipv4() {
             Domain::IPV4
@@ -150,27 +509,41 @@ impl ServerInner {
         }
 
         socket.set_nonblocking(true)?;
+
+        if let Some(keepalive) = &self.config.tcp_keepalive {
+            let params = socket2::TcpKeepalive::new()
+                .with_time(std::time::Duration::from_secs(keepalive.idle_secs as u64))
+                .with_interval(std::time::Duration::from_secs(keepalive.interval_secs as u64))
+                .with_retries(keepalive.retries);
+            socket.set_tcp_keepalive(&params)?;
+        }
+
+        #[cfg(target_os = "linux")]
+        if let Some(fastopen_backlog) = self.config.tcp_fastopen_backlog {
+            set_tcp_fastopen(&socket, fastopen_backlog)?;
+        }
+
         socket.bind(&addr.into())?;
-        socket.listen(8192)?; // Large backlog for high concurrency
+        socket.listen(self.config.backlog as i32)?;
 
         Ok(socket.into())
     }
 
+
+
     async fn handle_request(
         self: &Arc<Self>,
         client_addr: SocketAddr,
         req: Request,
     ) -> Response<Body> {
         let start = Instant::now();
-        self.stats
-            .active_connections
-            .fetch_add(1, Ordering::Relaxed);
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
 
         let response = self.process_request(client_addr, req).await;
 
-        self.stats
-            .active_connections
-            .fetch_sub(1, Ordering::Relaxed);
+        if self.in_flight.fetch_sub(1, Ordering::Relaxed) == 1 {
+            self.drained_notify.notify_waiters();
+        }
         self.stats.total_requests.fetch_add(1, Ordering::Relaxed);
         self.stats.total_latency_us.fetch_add(
             start.elapsed().as_micros() as u64,
@@ -183,7 +556,7 @@ impl ServerInner {
     async fn process_request(
         self: &Arc<Self>,
         client_addr: SocketAddr,
-        req: Request,
+        mut req: Request,
     ) -> Response<Body> {
         let handler = {
             let guard = self.handler.read();
@@ -200,30 +573,93 @@ impl ServerInner {
             }
         };
 
+        // Take the upgrade future before splitting the request, since it
+        // lives on the request's extensions
+        let upgrade_source = if is_websocket_upgrade(&req) {
+            Some(hyper::upgrade::on(&mut req))
+        } else {
+            None
+        };
+
         // Extract request parts
         let (parts, body) = req.into_parts();
 
-        // Collect body bytes (for bodies within size limit)
-        let body_bytes = match body.collect().await {
-            Ok(collected) => collected.to_bytes(),
-            Err(e) => {
-                return Response::builder()
-                    .status(StatusCode::BAD_REQUEST)
-                    .body(Body::from(format!("Failed to read body: {}", e)))
-                    .unwrap();
+        let accept_encoding = parts
+            .headers
+            .get(http::header::ACCEPT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+
+        let content_length = parts
+            .headers
+            .get(http::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+        let max_body_size = self.config.max_body_size as u64;
+
+        // Run request filters before doing any body I/O or calling the JS
+        // handler, so a rejection (rate limit, method allowlist, ...) is as
+        // cheap as possible.
+        let client_address = client_addr.to_string();
+        {
+            let filters = self.filters.read();
+            if !filters.is_empty() {
+                let request_parts = RequestParts {
+                    method: &parts.method,
+                    uri: &parts.uri,
+                    headers: &parts.headers,
+                    client_address: &client_address,
+                };
+                for filter in filters.iter() {
+                    if let FilterAction::ShortCircuit(response) = filter.on_request(&request_parts)
+                    {
+                        let response = apply_response_filters(response, &filters);
+                        return build_buffered_response(
+                            response,
+                            accept_encoding.as_deref(),
+                            &self.config.compression,
+                        );
+                    }
+                }
             }
-        };
+        }
 
-        // Check body size limit
-        if body_bytes.len() > self.config.max_body_size as usize {
+        // A declared length over the limit is rejected outright, before any
+        // body I/O, instead of being streamed through to the handler.
+        if content_length.is_some_and(|len| len > max_body_size) {
             return Response::builder()
                 .status(StatusCode::PAYLOAD_TOO_LARGE)
                 .body(Body::from("Request body too large"))
                 .unwrap();
         }
 
+        let (body_bytes, body_handle, body_truncated) = if should_stream_body(
+            content_length,
+            self.config.stream_threshold,
+        ) {
+            let body_handle = spawn_streaming_body(body, max_body_size);
+            let body_truncated = body_handle.truncated_flag();
+            (None, Some(body_handle), Some(body_truncated))
+        } else {
+            match collect_body_bounded(body, max_body_size).await {
+                Ok(BoundedBody::Complete(bytes)) => (Some(bytes), None, None),
+                Ok(BoundedBody::TooLarge) => {
+                    return Response::builder()
+                        .status(StatusCode::PAYLOAD_TOO_LARGE)
+                        .body(Body::from("Request body too large"))
+                        .unwrap();
+                }
+                Err(e) => {
+                    return Response::builder()
+                        .status(StatusCode::BAD_REQUEST)
+                        .body(Body::from(format!("Failed to read body: {}", e)))
+                        .unwrap();
+                }
+            }
+        };
+
         // Build full URL
-        let url = build_url(&parts);
+        let url = build_url(&parts, self.config.scheme);
 
         // Extract headers as vec of HeaderPair
         let headers: Vec<HeaderPair> = parts
@@ -236,19 +672,18 @@ impl ServerInner {
             .collect();
 
         // Create oneshot channel for response
-        let (tx, rx) = oneshot::channel::<JsResponse>();
+        let (tx, rx) = oneshot::channel::<ResponseMessage>();
 
         // Create request context
         let request_context = RequestContext {
             method: parts.method.to_string(),
             url,
             headers,
-            body: if body_bytes.is_empty() {
-                None
-            } else {
-                Some(napi::bindgen_prelude::Buffer::from(body_bytes.to_vec()))
-            },
-            client_address: client_addr.to_string(),
+            body: body_bytes.filter(|b| !b.is_empty()).map(|b| napi::bindgen_prelude::Buffer::from(b.to_vec())),
+            client_address,
+            encrypted: self.config.tls.is_some(),
+            body_handle: Mutex::new(body_handle),
+            upgrade_source: Mutex::new(upgrade_source),
             response_sender: Some(Arc::new(Mutex::new(Some(tx)))),
         };
 
@@ -256,13 +691,18 @@ impl ServerInner {
         handler.call(request_context, ThreadsafeFunctionCallMode::NonBlocking);
 
         // Wait for response from JavaScript
-        match tokio::time::timeout(
+        let response = match tokio::time::timeout(
             std::time::Duration::from_millis(self.config.timeout_ms as u64),
             rx,
         )
         .await
         {
-            Ok(Ok(js_response)) => build_response(js_response),
+            Ok(Ok(message)) => build_response(
+                message,
+                accept_encoding.as_deref(),
+                &self.config.compression,
+                &self.filters.read(),
+            ),
             Ok(Err(_)) => {
                 // Channel closed without response
                 Response::builder()
@@ -277,16 +717,90 @@ impl ServerInner {
                     .body(Body::from("Request timeout"))
                     .unwrap()
             }
+        };
+
+        // A client that sent more than its declared Content-Length on the
+        // streaming path isn't caught by the upfront Content-Length check;
+        // enforce 413 here regardless of what the handler returned, rather
+        // than relying on it to check `BodyHandle::isTruncated()` itself.
+        match body_truncated {
+            Some(truncated) if truncated.load(Ordering::Relaxed) => Response::builder()
+                .status(StatusCode::PAYLOAD_TOO_LARGE)
+                .body(Body::from("Request body too large"))
+                .unwrap(),
+            _ => response,
         }
     }
 
-    pub async fn close(&self) {
+    /// Stop accepting new connections and wait for in-flight requests to
+    /// drain. If `deadline_ms` is given and elapses before draining
+    /// completes, remaining connections are forcibly aborted.
+    pub async fn close(&self, deadline_ms: Option<u32>) -> CloseResult {
         self.shutdown_notify.notify_one();
+
+        match deadline_ms {
+            Some(ms) => {
+                match tokio::time::timeout(
+                    std::time::Duration::from_millis(ms as u64),
+                    self.wait_drained(),
+                )
+                .await
+                {
+                    Ok(()) => CloseResult {
+                        drained: true,
+                        ..Default::default()
+                    },
+                    Err(_) => {
+                        let pending_requests = self.in_flight.load(Ordering::Relaxed);
+                        let aborted_connections = self.abort_remaining_connections();
+                        CloseResult {
+                            drained: false,
+                            pending_requests,
+                            aborted_connections,
+                        }
+                    }
+                }
+            }
+            None => {
+                self.wait_drained().await;
+                CloseResult {
+                    drained: true,
+                    ..Default::default()
+                }
+            }
+        }
+    }
+
+    async fn wait_drained(&self) {
+        loop {
+            let notified = self.drained_notify.notified();
+            tokio::pin!(notified);
+            // Register as a waiter *before* checking `in_flight`, otherwise
+            // a `notify_waiters()` that fires between the check and the
+            // `.await` below (unlike `notify_one`, it stores no permit for
+            // a future waiter) would be missed forever.
+            notified.as_mut().enable();
+
+            if self.in_flight.load(Ordering::Relaxed) == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    fn abort_remaining_connections(&self) -> u32 {
+        let mut connections = self.connections.lock();
+        let count = connections.len() as u32;
+        for (_, handle) in connections.drain() {
+            handle.abort();
+        }
+        count
     }
 
     pub fn stats(&self) -> ServerStats {
         let total_requests = self.stats.total_requests.load(Ordering::Relaxed);
         let total_latency_us = self.stats.total_latency_us.load(Ordering::Relaxed);
+        let tcp_rtt_samples = self.stats.tcp_rtt_samples.load(Ordering::Relaxed);
 
         ServerStats {
             active_connections: self.stats.active_connections.load(Ordering::Relaxed),
@@ -297,12 +811,135 @@ impl ServerInner {
             } else {
                 0.0
             },
+            avg_tcp_rtt_us: if tcp_rtt_samples > 0 {
+                self.stats.tcp_rtt_us_sum.load(Ordering::Relaxed) as f64 / tcp_rtt_samples as f64
+            } else {
+                0.0
+            },
+            tcp_retransmits: self.stats.tcp_retransmits.load(Ordering::Relaxed) as f64,
         }
     }
 }
 
-fn build_url(parts: &http::request::Parts) -> String {
-    let scheme = "http"; // TODO: Support HTTPS
+/// Decide whether a request body should be streamed to the JS handler via
+/// `BodyHandle` rather than buffered up front. Only bodies whose declared
+/// length clears `stream_threshold` (see `ServerConfig::stream_threshold`)
+/// take the streaming path; bodies with an unknown length (chunked transfer
+/// encoding) are always buffered (via `collect_body_bounded`, which still
+/// enforces `max_body_size` incrementally) so a small chunked request
+/// doesn't unexpectedly lose `request.body` in favor of a `BodyHandle` it
+/// never asked for. Bodies whose declared length exceeds `max_body_size` are
+/// rejected before this is ever called.
+fn should_stream_body(content_length: Option<u64>, stream_threshold: u64) -> bool {
+    matches!(content_length, Some(len) if len >= stream_threshold)
+}
+
+/// Outcome of `collect_body_bounded`
+enum BoundedBody {
+    Complete(Bytes),
+    TooLarge,
+}
+
+/// Buffer a request body in memory, enforcing `max_body_size` as chunks
+/// arrive instead of reading the whole body before checking its length —
+/// the latter would let an unbounded/chunked body exhaust memory before the
+/// size check ever ran.
+async fn collect_body_bounded(
+    body: Body,
+    max_body_size: u64,
+) -> Result<BoundedBody, axum::Error> {
+    let mut stream = body.into_data_stream();
+    let mut buf = Vec::new();
+
+    while let Some(frame) = stream.next().await {
+        let chunk = frame?;
+        if buf.len() as u64 + chunk.len() as u64 > max_body_size {
+            return Ok(BoundedBody::TooLarge);
+        }
+        buf.extend_from_slice(&chunk);
+    }
+
+    Ok(BoundedBody::Complete(Bytes::from(buf)))
+}
+
+/// Spawn a task that forwards body frames into a channel as they arrive.
+/// The body's declared `Content-Length` is already checked against
+/// `max_body_size` before this is called, so the `total > max_body_size`
+/// check here is only a defense against a client that sends more than it
+/// declared; hitting it marks the resulting `BodyHandle` as truncated and
+/// stops forwarding further chunks.
+fn spawn_streaming_body(body: Body, max_body_size: u64) -> BodyHandle {
+    let (tx, rx) = tokio::sync::mpsc::channel::<Bytes>(32);
+    let truncated = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let truncated_task = Arc::clone(&truncated);
+
+    tokio::spawn(async move {
+        let mut stream = body.into_data_stream();
+        let mut total: u64 = 0;
+
+        while let Some(frame) = stream.next().await {
+            let chunk = match frame {
+                Ok(chunk) => chunk,
+                Err(_) => break,
+            };
+
+            total += chunk.len() as u64;
+            if total > max_body_size {
+                truncated_task.store(true, Ordering::Relaxed);
+                break;
+            }
+
+            if tx.send(chunk).await.is_err() {
+                // Receiver dropped; handler stopped reading.
+                break;
+            }
+        }
+    });
+
+    BodyHandle::with_truncated_flag(rx, truncated)
+}
+
+/// Ensures a process-default `CryptoProvider` is installed before any
+/// `rustls::ServerConfig::builder()` call. Without one, rustls 0.23 panics
+/// at runtime rather than erroring (the common footgun when a crate enables
+/// both the `ring` and `aws-lc-rs` backends, or neither); installing `ring`
+/// explicitly here avoids that panic. Requires the `ring` feature on the
+/// `rustls` dependency (without it this does not compile); an error from
+/// `install_default` (a provider is already installed) is ignored.
+static CRYPTO_PROVIDER_INIT: std::sync::Once = std::sync::Once::new();
+
+fn ensure_crypto_provider_installed() {
+    CRYPTO_PROVIDER_INIT.call_once(|| {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+    });
+}
+
+/// Load a `rustls::ServerConfig` from the PEM cert chain/key paths in
+/// `TlsOptions`
+fn build_rustls_config(tls: &TlsConfig) -> std::io::Result<rustls::ServerConfig> {
+    ensure_crypto_provider_installed();
+
+    let cert_file = std::fs::File::open(&tls.cert_path)?;
+    let certs = rustls_pemfile::certs(&mut std::io::BufReader::new(cert_file))
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    let key_file = std::fs::File::open(&tls.key_path)?;
+    let key = rustls_pemfile::private_key(&mut std::io::BufReader::new(key_file))?.ok_or_else(
+        || {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "no private key found in key_path",
+            )
+        },
+    )?;
+
+    rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))
+}
+
+fn build_url(parts: &http::request::Parts, scheme: &str) -> String {
     let host = parts
         .headers
         .get("host")
@@ -317,17 +954,248 @@ fn build_url(parts: &http::request::Parts) -> String {
     format!("{}://{}{}", scheme, host, path_and_query)
 }
 
-fn build_response(js_response: JsResponse) -> Response<Body> {
-    let mut builder = Response::builder().status(js_response.status);
+/// Run every registered filter's `on_response` hook over a buffered
+/// response, in registration order.
+fn apply_response_filters(mut response: JsResponse, filters: &[Box<dyn Filter>]) -> JsResponse {
+    for filter in filters {
+        filter.on_response(&mut response);
+    }
+    response
+}
+
+fn build_response(
+    message: ResponseMessage,
+    accept_encoding: Option<&str>,
+    compression: &CompressionConfig,
+    filters: &[Box<dyn Filter>],
+) -> Response<Body> {
+    match message {
+        ResponseMessage::Buffered(js_response) => {
+            let js_response = apply_response_filters(js_response, filters);
+            build_buffered_response(js_response, accept_encoding, compression)
+        }
+        ResponseMessage::Streaming {
+            status,
+            headers,
+            receiver,
+        } => {
+            let filtered = apply_response_filters(
+                JsResponse {
+                    status,
+                    headers,
+                    body: None,
+                },
+                filters,
+            );
+
+            let mut builder = Response::builder().status(filtered.status);
+            for header in &filtered.headers {
+                // The body is sent chunked as it streams in, so a
+                // handler/filter-supplied Content-Length (which can't know
+                // the eventual total) would advertise a length the body
+                // never matches; drop it rather than forward it verbatim.
+                if header.name.eq_ignore_ascii_case("content-length") {
+                    continue;
+                }
+                builder = builder.header(header.name.as_str(), header.value.as_str());
+            }
 
-    for header in js_response.headers {
-        builder = builder.header(header.name, header.value);
+            let stream = ReceiverStream::new(receiver).map(Ok::<Bytes, std::io::Error>);
+            builder.body(Body::from_stream(stream)).unwrap()
+        }
+        ResponseMessage::Upgrade { accept } => {
+            let filtered = apply_response_filters(
+                JsResponse {
+                    status: StatusCode::SWITCHING_PROTOCOLS.as_u16(),
+                    headers: vec![
+                        JsHeader {
+                            name: http::header::CONNECTION.to_string(),
+                            value: "Upgrade".to_string(),
+                        },
+                        JsHeader {
+                            name: http::header::UPGRADE.to_string(),
+                            value: "websocket".to_string(),
+                        },
+                        JsHeader {
+                            name: "Sec-WebSocket-Accept".to_string(),
+                            value: accept,
+                        },
+                    ],
+                    body: None,
+                },
+                filters,
+            );
+
+            let mut builder = Response::builder().status(filtered.status);
+            for header in &filtered.headers {
+                builder = builder.header(header.name.as_str(), header.value.as_str());
+            }
+            builder.body(Body::empty()).unwrap()
+        }
     }
+}
 
-    let body = match js_response.body {
-        Some(buffer) => Body::from(Bytes::from(buffer.to_vec())),
-        None => Body::empty(),
+/// Check whether a request is a valid WebSocket handshake per RFC 6455:
+/// `GET` with `Connection: Upgrade`, `Upgrade: websocket`,
+/// `Sec-WebSocket-Version: 13`, and a `Sec-WebSocket-Key`.
+fn is_websocket_upgrade(req: &Request) -> bool {
+    if req.method() != axum::http::Method::GET {
+        return false;
+    }
+
+    let headers = req.headers();
+
+    let has_upgrade_connection = headers
+        .get(http::header::CONNECTION)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.split(',').any(|token| token.trim().eq_ignore_ascii_case("upgrade")))
+        .unwrap_or(false);
+
+    let is_websocket = headers
+        .get(http::header::UPGRADE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.eq_ignore_ascii_case("websocket"))
+        .unwrap_or(false);
+
+    let version_13 = headers
+        .get("sec-websocket-version")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim() == "13")
+        .unwrap_or(false);
+
+    let has_key = headers.contains_key("sec-websocket-key");
+
+    has_upgrade_connection && is_websocket && version_13 && has_key
+}
+
+fn build_buffered_response(
+    js_response: JsResponse,
+    accept_encoding: Option<&str>,
+    compression: &CompressionConfig,
+) -> Response<Body> {
+    let has_content_encoding = js_response
+        .headers
+        .iter()
+        .any(|h| h.name.eq_ignore_ascii_case("content-encoding"));
+    let is_compressible = js_response
+        .headers
+        .iter()
+        .find(|h| h.name.eq_ignore_ascii_case("content-type"))
+        .map(|h| is_content_compressible(&h.value))
+        .unwrap_or(false);
+
+    let body_bytes = js_response.body.map(|b| b.to_vec()).unwrap_or_default();
+
+    let encoding = if compression.enabled
+        && !has_content_encoding
+        && is_compressible
+        && body_bytes.len() >= compression.min_size
+    {
+        pick_encoding(accept_encoding)
+    } else {
+        None
     };
 
-    builder.body(body).unwrap()
+    let mut builder = Response::builder().status(js_response.status);
+    for header in &js_response.headers {
+        // When we're about to compress, we set content-length/
+        // content-encoding ourselves below based on the compressed body;
+        // forwarding the handler's original copies too would produce
+        // conflicting duplicate headers.
+        if encoding.is_some() {
+            let name_lower = header.name.to_ascii_lowercase();
+            if name_lower == "content-length" || name_lower == "content-encoding" {
+                continue;
+            }
+        }
+        builder = builder.header(header.name.as_str(), header.value.as_str());
+    }
+
+    match encoding {
+        Some(encoding) => {
+            let compressed = compress_bytes(&body_bytes, encoding);
+            builder = builder
+                .header("content-encoding", encoding.as_str())
+                .header("content-length", compressed.len().to_string())
+                .header("vary", "Accept-Encoding");
+            builder.body(Body::from(compressed)).unwrap()
+        }
+        None => {
+            let body = if body_bytes.is_empty() {
+                Body::empty()
+            } else {
+                Body::from(Bytes::from(body_bytes))
+            };
+            builder.body(body).unwrap()
+        }
+    }
+}
+
+/// Content types worth spending CPU to compress. Mirrors the "compressible
+/// by default" set used by most HTTP servers: text-ish and structured-text
+/// formats, but not formats that are already compressed (images, video,
+/// archives).
+fn is_content_compressible(content_type: &str) -> bool {
+    let content_type = content_type.split(';').next().unwrap_or(content_type).trim();
+
+    content_type.starts_with("text/")
+        || matches!(
+            content_type,
+            "application/json"
+                | "application/javascript"
+                | "application/xml"
+                | "application/xhtml+xml"
+                | "image/svg+xml"
+        )
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ContentEncoding {
+    Brotli,
+    Gzip,
+}
+
+impl ContentEncoding {
+    fn as_str(self) -> &'static str {
+        match self {
+            ContentEncoding::Brotli => "br",
+            ContentEncoding::Gzip => "gzip",
+        }
+    }
+}
+
+/// Pick the best encoding the client advertised in `Accept-Encoding`,
+/// preferring brotli over gzip.
+fn pick_encoding(accept_encoding: Option<&str>) -> Option<ContentEncoding> {
+    let accept_encoding = accept_encoding?;
+    let offers: Vec<&str> = accept_encoding
+        .split(',')
+        .map(|v| v.split(';').next().unwrap_or(v).trim())
+        .collect();
+
+    if offers.contains(&"br") {
+        Some(ContentEncoding::Brotli)
+    } else if offers.contains(&"gzip") {
+        Some(ContentEncoding::Gzip)
+    } else {
+        None
+    }
+}
+
+fn compress_bytes(data: &[u8], encoding: ContentEncoding) -> Vec<u8> {
+    match encoding {
+        ContentEncoding::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data).expect("in-memory gzip write");
+            encoder.finish().expect("in-memory gzip finish")
+        }
+        ContentEncoding::Brotli => {
+            let mut output = Vec::new();
+            let params = brotli::enc::BrotliEncoderParams::default();
+            brotli::BrotliCompress(&mut std::io::Cursor::new(data), &mut output, &params)
+                .expect("in-memory brotli compress");
+            output
+        }
+    }
 }
@@ -0,0 +1,255 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hyper::upgrade::Upgraded;
+use hyper_util::rt::TokioIo;
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use sha1::{Digest, Sha1};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::Mutex;
+
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+const CLOSE_MESSAGE_TOO_BIG: u16 = 1009;
+
+/// Largest payload this server will allocate for a single incoming frame,
+/// and (since fragments of one logical message are reassembled by `recv`)
+/// the largest total size a fragmented message may reach. Anything larger
+/// is rejected with a 1009 close rather than trusting the
+/// attacker-controlled length prefix.
+const MAX_FRAME_PAYLOAD_LEN: u64 = 16 * 1024 * 1024;
+
+/// Compute the `Sec-WebSocket-Accept` value for a client's
+/// `Sec-WebSocket-Key`, per RFC 6455 section 1.3.
+pub(crate) fn accept_key(client_key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(client_key.as_bytes());
+    hasher.update(WEBSOCKET_GUID.as_bytes());
+    STANDARD.encode(hasher.finalize())
+}
+
+/// A raw WebSocket connection handed to JS after `RequestContext::upgrade()`.
+/// Frames payloads per RFC 6455 (ping/pong/close are handled transparently
+/// by `recv`); any sub-protocol on top is left to the JS side.
+#[napi]
+pub struct WebSocketHandle {
+    io: Mutex<Option<TokioIo<Upgraded>>>,
+}
+
+impl WebSocketHandle {
+    pub(crate) fn new(upgraded: Upgraded) -> Self {
+        Self {
+            io: Mutex::new(Some(TokioIo::new(upgraded))),
+        }
+    }
+}
+
+#[napi]
+impl WebSocketHandle {
+    /// Send a binary frame
+    #[napi]
+    pub async fn send(&self, data: Buffer) -> Result<()> {
+        self.write_frame(OPCODE_BINARY, &data).await
+    }
+
+    /// Send a text frame
+    #[napi(js_name = "sendText")]
+    pub async fn send_text(&self, text: String) -> Result<()> {
+        self.write_frame(OPCODE_TEXT, text.as_bytes()).await
+    }
+
+    /// Receive the next complete message's payload. Returns null once the
+    /// connection is closed (a received close frame is answered and then
+    /// surfaced as `None`; pings/pongs are handled transparently). Fragmented
+    /// messages (a data frame with FIN=0 followed by continuation frames) are
+    /// reassembled here before being returned; control frames may still
+    /// interleave between fragments per RFC 6455 section 5.4. Reserved
+    /// opcodes and out-of-order continuation frames fail the connection with
+    /// a 1002 close instead of being surfaced as data.
+    #[napi]
+    pub async fn recv(&self) -> Result<Option<Buffer>> {
+        let mut fragments: Option<Vec<u8>> = None;
+
+        loop {
+            let (fin, opcode, payload) = match self.read_frame().await? {
+                None => return Ok(None),
+                Some(frame) => frame,
+            };
+
+            match opcode {
+                OPCODE_CONTINUATION => {
+                    let buf = match fragments.as_mut() {
+                        Some(buf) => buf,
+                        None => {
+                            self.fail_connection(CLOSE_PROTOCOL_ERROR).await;
+                            return Ok(None);
+                        }
+                    };
+                    if buf.len() as u64 + payload.len() as u64 > MAX_FRAME_PAYLOAD_LEN {
+                        self.fail_connection(CLOSE_MESSAGE_TOO_BIG).await;
+                        return Ok(None);
+                    }
+                    buf.extend_from_slice(&payload);
+                    if fin {
+                        return Ok(Some(Buffer::from(fragments.take().unwrap())));
+                    }
+                }
+                OPCODE_TEXT | OPCODE_BINARY => {
+                    if fragments.is_some() {
+                        // A new data frame can't start while a fragmented
+                        // message is still awaiting its continuation.
+                        self.fail_connection(CLOSE_PROTOCOL_ERROR).await;
+                        return Ok(None);
+                    }
+                    if fin {
+                        return Ok(Some(Buffer::from(payload)));
+                    }
+                    fragments = Some(payload);
+                }
+                OPCODE_CLOSE => {
+                    let _ = self.write_frame(OPCODE_CLOSE, &[]).await;
+                    self.io.lock().await.take();
+                    return Ok(None);
+                }
+                OPCODE_PING => {
+                    self.write_frame(OPCODE_PONG, &payload).await?;
+                }
+                OPCODE_PONG => {}
+                _ => {
+                    // Reserved opcode (0x3-0x7 data, 0xB-0xF control):
+                    // undefined by RFC 6455, so fail rather than surface it.
+                    self.fail_connection(CLOSE_PROTOCOL_ERROR).await;
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    /// Close the connection with an RFC 6455 close frame
+    #[napi]
+    pub async fn close(&self) -> Result<()> {
+        let _ = self.write_frame(OPCODE_CLOSE, &[]).await;
+        self.io.lock().await.take();
+        Ok(())
+    }
+}
+
+impl WebSocketHandle {
+    async fn write_frame(&self, opcode: u8, payload: &[u8]) -> Result<()> {
+        let mut guard = self.io.lock().await;
+        let io = guard
+            .as_mut()
+            .ok_or_else(|| Error::new(Status::GenericFailure, "WebSocket is closed"))?;
+        io.write_all(&encode_frame(opcode, payload))
+            .await
+            .map_err(|e| Error::new(Status::GenericFailure, format!("WebSocket write failed: {}", e)))
+    }
+
+    /// Send a close frame carrying `code` and drop the connection. Used by
+    /// `recv` to fail the connection on a protocol violation discovered
+    /// after a frame has already been read (and so the read lock released).
+    async fn fail_connection(&self, code: u16) {
+        let _ = self.write_frame(OPCODE_CLOSE, &close_payload(code)).await;
+        self.io.lock().await.take();
+    }
+
+    /// Read one frame, returning its FIN bit, opcode, and unmasked payload.
+    /// Server-side frames from a conforming client are always masked (RFC
+    /// 6455 section 5.1); fragmentation (FIN=0, continuation frames) is
+    /// reassembled by the caller. Unmasked frames and frames advertising a
+    /// payload larger than `MAX_FRAME_PAYLOAD_LEN` are rejected with a close
+    /// frame rather than trusted, since the length prefix is otherwise
+    /// attacker-controlled input to a `Vec` allocation.
+    async fn read_frame(&self) -> Result<Option<(bool, u8, Vec<u8>)>> {
+        let mut guard = self.io.lock().await;
+        let io = match guard.as_mut() {
+            Some(io) => io,
+            None => return Ok(None),
+        };
+
+        let mut header = [0u8; 2];
+        if io.read_exact(&mut header).await.is_err() {
+            return Ok(None);
+        }
+
+        let fin = header[0] & 0x80 != 0;
+        let opcode = header[0] & 0x0F;
+        let masked = header[1] & 0x80 != 0;
+        let mut len = (header[1] & 0x7F) as u64;
+
+        if len == 126 {
+            let mut ext = [0u8; 2];
+            io.read_exact(&mut ext).await.map_err(read_err)?;
+            len = u16::from_be_bytes(ext) as u64;
+        } else if len == 127 {
+            let mut ext = [0u8; 8];
+            io.read_exact(&mut ext).await.map_err(read_err)?;
+            len = u64::from_be_bytes(ext);
+        }
+
+        if !masked {
+            io.write_all(&encode_frame(OPCODE_CLOSE, &close_payload(CLOSE_PROTOCOL_ERROR)))
+                .await
+                .ok();
+            *guard = None;
+            return Ok(None);
+        }
+
+        if len > MAX_FRAME_PAYLOAD_LEN {
+            io.write_all(&encode_frame(
+                OPCODE_CLOSE,
+                &close_payload(CLOSE_MESSAGE_TOO_BIG),
+            ))
+            .await
+            .ok();
+            *guard = None;
+            return Ok(None);
+        }
+
+        let mut mask = [0u8; 4];
+        io.read_exact(&mut mask).await.map_err(read_err)?;
+
+        let mut payload = vec![0u8; len as usize];
+        io.read_exact(&mut payload).await.map_err(read_err)?;
+
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+
+        Ok(Some((fin, opcode, payload)))
+    }
+}
+
+/// Build a close frame payload carrying the given status code (RFC 6455
+/// section 7.4)
+fn close_payload(code: u16) -> [u8; 2] {
+    code.to_be_bytes()
+}
+
+fn encode_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | opcode); // FIN set, server never fragments its own frames
+    let len = payload.len();
+    if len < 126 {
+        frame.push(len as u8);
+    } else if len <= u16::MAX as usize {
+        frame.push(126);
+        frame.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        frame.push(127);
+        frame.extend_from_slice(&(len as u64).to_be_bytes());
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+fn read_err(e: std::io::Error) -> Error {
+    Error::new(Status::GenericFailure, format!("WebSocket read failed: {}", e))
+}